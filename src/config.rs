@@ -0,0 +1,121 @@
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::warn;
+
+/// Describes how to fetch and extract the two power samples from a panel's
+/// scrape target, so the exporter isn't wired to one specific firmware.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScrapeConfig {
+    /// Charset to decode the HTTP response body with, e.g. `"shift_jis"`
+    /// or `"utf-8"`.
+    #[serde(default = "default_charset")]
+    pub charset: String,
+    /// Factor each extracted sample is multiplied by before it's exposed
+    /// as watts.
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+    #[serde(flatten)]
+    pub extraction: Extraction,
+}
+
+fn default_charset() -> String {
+    "utf-8".to_string()
+}
+
+fn default_scale() -> f64 {
+    1000.0
+}
+
+/// How to pull the raw generate/consumption values out of a scrape body.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Extraction {
+    /// Extract two values delimited by start/end marker strings, as the
+    /// original solar-panel-exporter firmware embeds them in HTML
+    /// comments.
+    Markers {
+        generate_start: String,
+        generate_end: String,
+        consumption_start: String,
+        consumption_end: String,
+    },
+    /// Extract the `generate` and `consumption` named capture groups from
+    /// a single regex run against the raw body.
+    Regex { pattern: String },
+    /// Extract two JSON Pointers (RFC 6901) from a JSON body.
+    Json {
+        generate_pointer: String,
+        consumption_pointer: String,
+    },
+}
+
+impl ScrapeConfig {
+    /// Loads a scrape config from `path`, then layers a handful of env
+    /// vars on top (`SOLAR_SCRAPE_CHARSET`, `SOLAR_SCRAPE_SCALE`, and,
+    /// when the extraction mode is `Markers`, `SOLAR_GENERATE_START_MARKER`
+    /// / `SOLAR_GENERATE_END_MARKER` / `SOLAR_CONSUMPTION_START_MARKER` /
+    /// `SOLAR_CONSUMPTION_END_MARKER`), so an env var takes precedence
+    /// over the file. With no path given, falls back to the original hard-coded Shift-JIS marker
+    /// format so existing deployments keep working unconfigured.
+    pub fn init(path: Option<&Path>) -> anyhow::Result<Self> {
+        let mut config = match path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)?;
+                toml::from_str(&contents)?
+            }
+            None => Self::legacy_default(),
+        };
+
+        config.apply_env_overrides();
+        Ok(config)
+    }
+
+    fn apply_env_overrides(&mut self) {
+        if let Ok(charset) = std::env::var("SOLAR_SCRAPE_CHARSET") {
+            self.charset = charset;
+        }
+
+        if let Ok(scale) = std::env::var("SOLAR_SCRAPE_SCALE") {
+            match scale.parse() {
+                Ok(scale) => self.scale = scale,
+                Err(e) => warn!("invalid SOLAR_SCRAPE_SCALE {:?}: {}", scale, e),
+            }
+        }
+
+        if let Extraction::Markers {
+            generate_start,
+            generate_end,
+            consumption_start,
+            consumption_end,
+        } = &mut self.extraction
+        {
+            if let Ok(v) = std::env::var("SOLAR_GENERATE_START_MARKER") {
+                *generate_start = v;
+            }
+            if let Ok(v) = std::env::var("SOLAR_GENERATE_END_MARKER") {
+                *generate_end = v;
+            }
+            if let Ok(v) = std::env::var("SOLAR_CONSUMPTION_START_MARKER") {
+                *consumption_start = v;
+            }
+            if let Ok(v) = std::env::var("SOLAR_CONSUMPTION_END_MARKER") {
+                *consumption_end = v;
+            }
+        }
+    }
+
+    fn legacy_default() -> Self {
+        Self {
+            charset: "shift_jis".to_string(),
+            scale: 1000.0,
+            extraction: Extraction::Markers {
+                generate_start: "<!-- ここから発電量表示 -->".to_string(),
+                generate_end: "<!-- ここまで発電量表示 -->".to_string(),
+                consumption_start: "<!-- ここから消費量表示 -->".to_string(),
+                consumption_end: "<!-- ここまで消費量表示 -->".to_string(),
+            },
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::str::FromStr;
+
+use prometheus_client::encoding::EncodeLabelSet;
+
+/// A single panel/inverter to scrape, as named on the command line with
+/// `--endpoint name=primary_url[,fallback_url]`.
+#[derive(Debug, Clone)]
+pub struct Device {
+    pub name: String,
+    pub primary: String,
+    pub fallback: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct DeviceParseError(String);
+
+impl fmt::Display for DeviceParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected `name=endpoint` or `name=endpoint,fallback_endpoint`, got {:?}",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for DeviceParseError {}
+
+impl FromStr for Device {
+    type Err = DeviceParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = s
+            .split_once('=')
+            .ok_or_else(|| DeviceParseError(s.to_string()))?;
+
+        if name.is_empty() || rest.is_empty() {
+            return Err(DeviceParseError(s.to_string()));
+        }
+
+        let (primary, fallback) = match rest.split_once(',') {
+            Some((primary, fallback)) => (primary, Some(fallback.to_string())),
+            None => (rest, None),
+        };
+
+        Ok(Device {
+            name: name.to_string(),
+            primary: primary.to_string(),
+            fallback,
+        })
+    }
+}
+
+/// The label set attached to every per-device metric series, e.g.
+/// `power_solar_generation_watts{device="roof_south"}`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DeviceLabel {
+    pub device: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_name_and_primary_endpoint() {
+        let device: Device = "roof_south=http://panel.local".parse().unwrap();
+        assert_eq!(device.name, "roof_south");
+        assert_eq!(device.primary, "http://panel.local");
+        assert_eq!(device.fallback, None);
+    }
+
+    #[test]
+    fn parses_name_primary_and_fallback_endpoint() {
+        let device: Device = "roof_south=http://a,http://b".parse().unwrap();
+        assert_eq!(device.name, "roof_south");
+        assert_eq!(device.primary, "http://a");
+        assert_eq!(device.fallback, Some("http://b".to_string()));
+    }
+
+    #[test]
+    fn rejects_input_without_an_equals_sign() {
+        assert!("http://panel.local".parse::<Device>().is_err());
+    }
+
+    #[test]
+    fn rejects_empty_name_or_endpoint() {
+        assert!("=http://panel.local".parse::<Device>().is_err());
+        assert!("roof_south=".parse::<Device>().is_err());
+    }
+}
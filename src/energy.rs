@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::Mutex;
+
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::device::DeviceLabel;
+
+/// Cumulative energy counters, integrated from sampled power by the
+/// background pollers. Counters only ever grow within the process
+/// lifetime; [`EnergyStore`] is what makes them survive a restart.
+#[derive(Clone, Default)]
+pub struct EnergyMetrics {
+    pub generated_watt_hours_total: Family<DeviceLabel, Counter<f64, AtomicU64>>,
+    pub consumed_watt_hours_total: Family<DeviceLabel, Counter<f64, AtomicU64>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeviceTotals {
+    pub generated_watt_hours: f64,
+    pub consumed_watt_hours: f64,
+}
+
+/// Persists each device's running energy totals to a small JSON state
+/// file on disk, so the counters don't reset to zero across restarts.
+pub struct EnergyStore {
+    path: PathBuf,
+    totals: Mutex<HashMap<String, DeviceTotals>>,
+}
+
+impl EnergyStore {
+    pub fn load(path: PathBuf) -> Self {
+        let totals = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            totals: Mutex::new(totals),
+        }
+    }
+
+    /// Returns the totals persisted for `device` so far, e.g. to seed a
+    /// counter on startup.
+    pub fn totals(&self, device: &str) -> DeviceTotals {
+        self.totals.lock().unwrap().get(device).cloned().unwrap_or_default()
+    }
+
+    /// Adds the given watt-hour deltas to `device`'s running totals and
+    /// persists the result to disk.
+    pub fn accumulate(&self, device: &str, generated_delta_wh: f64, consumed_delta_wh: f64) {
+        let mut totals = self.totals.lock().unwrap();
+        let entry = totals.entry(device.to_string()).or_default();
+        entry.generated_watt_hours += generated_delta_wh;
+        entry.consumed_watt_hours += consumed_delta_wh;
+
+        if let Err(e) = self.persist(&totals) {
+            error!("failed to persist energy totals to {:?}: {}", self.path, e);
+        }
+    }
+
+    fn persist(&self, totals: &HashMap<String, DeviceTotals>) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(totals)?;
+        fs::write(&self.path, json)
+    }
+}
+
+/// Trapezoidal-integrates a new power sample against the previous one to
+/// get a watt-hours delta. Returns `None` when there's no previous
+/// sample to integrate against (the first sample, or the sample right
+/// after a failed poll). A negative interval, e.g. from a clock
+/// adjustment, is clamped to zero rather than corrupting the total.
+pub fn integrate(previous: Option<(f64, f64)>, timestamp: f64, power: f64) -> Option<f64> {
+    let (prev_timestamp, prev_power) = previous?;
+    let dt_hours = (timestamp - prev_timestamp).max(0.0) / 3600.0;
+    Some(0.5 * (prev_power + power) * dt_hours)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_previous_sample_skips_integration() {
+        assert_eq!(integrate(None, 1_000.0, 500.0), None);
+    }
+
+    #[test]
+    fn trapezoidal_integration_over_an_hour() {
+        let delta = integrate(Some((0.0, 100.0)), 3_600.0, 300.0).unwrap();
+        assert_eq!(delta, 200.0);
+    }
+
+    #[test]
+    fn negative_dt_is_clamped_to_zero() {
+        let delta = integrate(Some((3_600.0, 100.0)), 0.0, 300.0).unwrap();
+        assert_eq!(delta, 0.0);
+    }
+}
@@ -0,0 +1,26 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use thiserror::Error;
+
+/// Failure modes surfaced while fetching, decoding, or parsing a panel's
+/// scrape target, or while encoding the `/metrics` response.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("failed to fetch panel endpoint: {0}")]
+    Fetch(#[from] reqwest::Error),
+    #[error("failed to parse body as JSON: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("marker or pointer {0:?} not found in panel response body")]
+    MarkerMissing(String),
+    #[error("failed to parse {0:?} as a float: {1}")]
+    ParseFloat(String, #[source] std::num::ParseFloatError),
+    #[error("failed to encode metrics: {0}")]
+    Encode(#[from] std::fmt::Error),
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        tracing::error!("{}", self);
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
@@ -1,35 +1,65 @@
+mod config;
+mod device;
+mod energy;
+mod error;
+mod poller;
+mod scrape;
+
 use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use axum::extract::{MatchedPath, State};
-use axum::http::{Request, StatusCode};
+use axum::http::{header, Request, StatusCode};
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::Router;
 use clap::Parser;
+use prometheus_client::encoding::text::encode;
+use prometheus_client::registry::Registry;
+use tokio_util::sync::CancellationToken;
 use tower_http::trace::TraceLayer;
-use tracing::{error, info, info_span, warn};
+use tracing::{info, info_span};
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::Layer;
 
+use config::ScrapeConfig;
+use device::Device;
+use energy::{EnergyMetrics, EnergyStore};
+use error::Error;
+use poller::PolledMetrics;
+
 #[derive(Parser, Debug)]
 struct Args {
-    #[arg(long)]
-    endpoint: String,
+    /// A panel device to scrape, formatted as `name=endpoint` or
+    /// `name=endpoint,fallback_endpoint`. May be repeated to monitor
+    /// several inverters from one process.
+    #[arg(long = "endpoint", required = true)]
+    endpoints: Vec<Device>,
     #[arg(long, env)]
     port: u16,
+    /// How often, in seconds, to poll each panel endpoint in the background.
+    #[arg(long, env, default_value_t = 15)]
+    poll_interval_secs: u64,
+    /// Path to a TOML file describing how to fetch and extract samples
+    /// from the panel's scrape target. Without it, the exporter falls
+    /// back to the original Shift-JIS HTML comment markers.
+    #[arg(long, env)]
+    scrape_config: Option<PathBuf>,
+    /// Path to the file where cumulative energy totals are persisted, so
+    /// they survive a restart instead of resetting to zero.
+    #[arg(long, env, default_value = "solar_energy_totals.json")]
+    energy_state_file: PathBuf,
 }
 
 #[derive(Clone)]
 struct AppState {
-    endpoint: String,
+    registry: Arc<Registry>,
 }
 
-const GENERATE_START_MARKER: &str = "<!-- ここから発電量表示 -->";
-const GENERATE_END_MARKER: &str = "<!-- ここまで発電量表示 -->";
-
-const CONSUMPTION_START_MARKER: &str = "<!-- ここから消費量表示 -->";
-const CONSUMPTION_END_MARKER: &str = "<!-- ここまで消費量表示 -->";
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
 
 #[tokio::main]
 async fn main() {
@@ -44,11 +74,81 @@ async fn main() {
 
     let config = Args::parse();
 
+    let scrape_config = Arc::new(
+        ScrapeConfig::init(config.scrape_config.as_deref())
+            .expect("failed to load scrape config"),
+    );
+
+    let metrics = PolledMetrics::default();
+    let energy_metrics = EnergyMetrics::default();
+    let energy_store = Arc::new(EnergyStore::load(config.energy_state_file));
+
+    let mut registry = Registry::default();
+    registry.register(
+        "power_solar_generation_watts",
+        "An amount of solar power generation in watts",
+        metrics.generation_watts.clone(),
+    );
+    registry.register(
+        "power_consumption_watts",
+        "An amount of power consumption in watts",
+        metrics.consumption_watts.clone(),
+    );
+    registry.register(
+        "solar_scrape_up",
+        "Whether the last poll of the panel endpoint succeeded",
+        metrics.scrape_up.clone(),
+    );
+    registry.register(
+        "solar_scrape_errors",
+        "Total number of failed polls of the panel endpoint",
+        metrics.scrape_errors_total.clone(),
+    );
+    registry.register(
+        "solar_last_scrape_timestamp_seconds",
+        "Unix timestamp of the last successful poll",
+        metrics.last_scrape_timestamp_seconds.clone(),
+    );
+    registry.register(
+        "solar_generated_watt_hours",
+        "Cumulative energy generated, integrated from sampled power",
+        energy_metrics.generated_watt_hours_total.clone(),
+    );
+    registry.register(
+        "solar_consumed_watt_hours",
+        "Cumulative energy consumed, integrated from sampled power",
+        energy_metrics.consumed_watt_hours_total.clone(),
+    );
+
+    let shutdown = CancellationToken::new();
+
+    let poll_interval = Duration::from_secs(config.poll_interval_secs);
+
+    // Bound each scrape by the poll interval so a panel that accepts the
+    // connection but never responds can't wedge a device's poller forever.
+    let http_client = reqwest::Client::builder()
+        .timeout(poll_interval)
+        .build()
+        .expect("failed to build HTTP client");
+
+    for device in config.endpoints {
+        tokio::spawn(poller::run(
+            device,
+            poll_interval,
+            scrape_config.clone(),
+            http_client.clone(),
+            metrics.clone(),
+            energy_metrics.clone(),
+            energy_store.clone(),
+            shutdown.clone(),
+        ));
+    }
+
     let app = Router::new()
-        .route("/metrics", get(metrics))
+        .route("/metrics", get(metrics_handler))
         .fallback(handler_404)
         .with_state(AppState {
-            endpoint: config.endpoint,
+            registry: Arc::new(registry),
         })
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
@@ -72,115 +172,55 @@ async fn main() {
         .await
         .unwrap();
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal(shutdown))
+        .await
+        .unwrap();
 }
 
-async fn metrics(state: State<AppState>) -> (StatusCode, String) {
-    info!("collecting metrics...");
-
-    let mut retry_count = 0u32;
-
-    let value = loop {
-        let body = match get_body(&state.endpoint).await {
-            Ok(body) => body,
-            Err(e) => {
-                error!("failed to fetch metrics: {}", e);
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    "failed to fetch metrics".to_string(),
-                );
-            }
-        };
-
-        if let Some(value) = parse(body) {
-            break Some(value);
-        }
-
-        retry_count += 1;
-
-        if retry_count > 3 {
-            break None;
-        }
-
-        warn!(
-            "retrying to fetch metrics... (retry_count: {})",
-            retry_count
-        );
-        tokio::time::sleep(std::time::Duration::from_secs(2u64.pow(retry_count))).await;
-    };
-
-    let Some((generate, consumption)) = value else {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            "failed to parse metrics".to_string(),
-        );
-    };
-
-    let response = [
-        "# HELP power_solar_generation_watts An amount of solar power generation in watts"
-            .to_string(),
-        "# TYPE power_solar_generation_watts gauge".to_string(),
-        format!("power_solar_generation_watts {}", generate),
-        "# HELP power_consumption_watts An amount of power consumption in watts".to_string(),
-        "# TYPE power_consumption_watts gauge".to_string(),
-        format!("power_consumption_watts {}", consumption),
-    ];
-    (StatusCode::OK, response.join("\n"))
+async fn metrics_handler(
+    state: State<AppState>,
+) -> Result<(StatusCode, [(header::HeaderName, &'static str); 1], String), Error> {
+    let mut buffer = String::new();
+    encode(&mut buffer, &state.registry)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+        buffer,
+    ))
 }
 
-async fn get_body(ep: &str) -> Result<String, reqwest::Error> {
-    reqwest::get(ep).await?.text_with_charset("shift_jis").await
+async fn handler_404() -> impl IntoResponse {
+    (StatusCode::NOT_FOUND, "404 Not Found. Try /metrics")
 }
 
-fn parse(body: String) -> Option<(i64, i64)> {
-    let generate = body
-        .lines()
-        .filter_map(|line| {
-            if !line.contains(GENERATE_START_MARKER) {
-                return None;
-            }
-
-            let end = line.find(GENERATE_END_MARKER)?;
-
-            Some(line[GENERATE_START_MARKER.len()..end].to_string())
-        })
-        .next();
-
-    let consumption = body
-        .lines()
-        .filter_map(|line| {
-            if !line.contains(CONSUMPTION_START_MARKER) {
-                return None;
-            }
-
-            let end = line.find(CONSUMPTION_END_MARKER)?;
-
-            Some(line[CONSUMPTION_START_MARKER.len()..end].to_string())
-        })
-        .next();
-
-    let (Some(generate), Some(consumption)) = (generate, consumption) else {
-        error!("failed to parse metrics. generate or consumption is missing.",);
-        error!("body: {:?}", body);
-        return None;
+/// Resolves once a Ctrl+C or SIGTERM is received, cancelling `shutdown` so
+/// the background pollers stop their loops before the listener itself
+/// shuts down.
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
     };
 
-    let Ok(generate) = generate.parse::<f64>().map(|v| (v * 1000.0).round() as i64) else {
-        error!("failed to parse generate as a float: {}", generate);
-        return None;
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
     };
 
-    let Ok(consumption) = consumption
-        .parse::<f64>()
-        .map(|v| (v * 1000.0).round() as i64)
-    else {
-        error!("failed to parse consumption as a float: {}", consumption);
-        return None;
-    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
 
-    Some((generate, consumption))
-}
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 
-async fn handler_404() -> impl IntoResponse {
-    (StatusCode::NOT_FOUND, "404 Not Found. Try /metrics")
+    info!("shutdown signal received, shutting down gracefully");
+    shutdown.cancel();
 }
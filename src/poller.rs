@@ -0,0 +1,157 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::config::ScrapeConfig;
+use crate::device::{Device, DeviceLabel};
+use crate::energy::{self, EnergyMetrics, EnergyStore};
+use crate::error::Error;
+use crate::scrape::{get_body, parse};
+
+/// Metrics kept up to date by the background pollers and read by the
+/// `/metrics` handler. Every series is keyed by `DeviceLabel`, so cloning
+/// is cheap: each `Family` is an `Arc`-backed map under the hood.
+#[derive(Clone, Default)]
+pub struct PolledMetrics {
+    pub generation_watts: Family<DeviceLabel, Gauge<i64>>,
+    pub consumption_watts: Family<DeviceLabel, Gauge<i64>>,
+    pub scrape_up: Family<DeviceLabel, Gauge<i64>>,
+    pub scrape_errors_total: Family<DeviceLabel, Counter>,
+    pub last_scrape_timestamp_seconds: Family<DeviceLabel, Gauge<f64, AtomicU64>>,
+}
+
+/// Polls a single device's endpoint (falling back to its `fallback`
+/// endpoint if set) on a fixed interval until `shutdown` is cancelled,
+/// updating `metrics` for that device's label in place. A failed poll
+/// never panics or stalls the loop; it just marks that device's
+/// `solar_scrape_up` as down and waits for the next tick.
+pub async fn run(
+    device: Device,
+    interval: Duration,
+    config: Arc<ScrapeConfig>,
+    http_client: reqwest::Client,
+    metrics: PolledMetrics,
+    energy_metrics: EnergyMetrics,
+    energy_store: Arc<EnergyStore>,
+    shutdown: CancellationToken,
+) {
+    let label = DeviceLabel {
+        device: device.name.clone(),
+    };
+
+    let initial_totals = energy_store.totals(&device.name);
+    energy_metrics
+        .generated_watt_hours_total
+        .get_or_create(&label)
+        .inc_by(initial_totals.generated_watt_hours);
+    energy_metrics
+        .consumed_watt_hours_total
+        .get_or_create(&label)
+        .inc_by(initial_totals.consumed_watt_hours);
+
+    // The last successful (timestamp, power) sample for each series, used
+    // to trapezoidally integrate power into energy. Cleared whenever a
+    // poll fails so we never integrate across the resulting gap.
+    let mut last_generation: Option<(f64, f64)> = None;
+    let mut last_consumption: Option<(f64, f64)> = None;
+
+    loop {
+        match scrape_once(&device, &config, &http_client).await {
+            Ok((generate, consumption)) => {
+                metrics
+                    .generation_watts
+                    .get_or_create(&label)
+                    .set(generate);
+                metrics
+                    .consumption_watts
+                    .get_or_create(&label)
+                    .set(consumption);
+                metrics.scrape_up.get_or_create(&label).set(1);
+                let now = now_as_unix_seconds();
+                metrics
+                    .last_scrape_timestamp_seconds
+                    .get_or_create(&label)
+                    .set(now);
+                info!(
+                    "poll succeeded for device {:?}: generate={}W consumption={}W",
+                    device.name, generate, consumption
+                );
+
+                let generated_delta_wh = energy::integrate(last_generation, now, generate as f64);
+                let consumed_delta_wh = energy::integrate(last_consumption, now, consumption as f64);
+                last_generation = Some((now, generate as f64));
+                last_consumption = Some((now, consumption as f64));
+
+                if let (Some(generated_delta_wh), Some(consumed_delta_wh)) =
+                    (generated_delta_wh, consumed_delta_wh)
+                {
+                    energy_metrics
+                        .generated_watt_hours_total
+                        .get_or_create(&label)
+                        .inc_by(generated_delta_wh);
+                    energy_metrics
+                        .consumed_watt_hours_total
+                        .get_or_create(&label)
+                        .inc_by(consumed_delta_wh);
+                    energy_store.accumulate(&device.name, generated_delta_wh, consumed_delta_wh);
+                }
+            }
+            Err(e) => {
+                error!("poll failed for device {:?}: {}", device.name, e);
+                metrics.scrape_up.get_or_create(&label).set(0);
+                metrics.scrape_errors_total.get_or_create(&label).inc();
+                last_generation = None;
+                last_consumption = None;
+            }
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    info!("poller for device {:?} stopped", device.name);
+}
+
+async fn scrape_once(
+    device: &Device,
+    config: &ScrapeConfig,
+    http_client: &reqwest::Client,
+) -> Result<(i64, i64), Error> {
+    match scrape_endpoint(&device.primary, config, http_client).await {
+        Ok(value) => Ok(value),
+        Err(primary_err) => match &device.fallback {
+            Some(fallback) => {
+                warn!(
+                    "primary endpoint failed for device {:?} ({}), trying fallback",
+                    device.name, primary_err
+                );
+                scrape_endpoint(fallback, config, http_client).await
+            }
+            None => Err(primary_err),
+        },
+    }
+}
+
+async fn scrape_endpoint(
+    endpoint: &str,
+    config: &ScrapeConfig,
+    http_client: &reqwest::Client,
+) -> Result<(i64, i64), Error> {
+    let body = get_body(http_client, endpoint, &config.charset).await?;
+    parse(body, config)
+}
+
+fn now_as_unix_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
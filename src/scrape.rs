@@ -0,0 +1,93 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::{Extraction, ScrapeConfig};
+use crate::error::Error;
+
+pub async fn get_body(client: &reqwest::Client, ep: &str, charset: &str) -> Result<String, Error> {
+    Ok(client
+        .get(ep)
+        .send()
+        .await?
+        .text_with_charset(charset)
+        .await?)
+}
+
+pub fn parse(body: String, config: &ScrapeConfig) -> Result<(i64, i64), Error> {
+    let (generate, consumption) = match &config.extraction {
+        Extraction::Markers {
+            generate_start,
+            generate_end,
+            consumption_start,
+            consumption_end,
+        } => (
+            extract_marked(&body, generate_start, generate_end)?,
+            extract_marked(&body, consumption_start, consumption_end)?,
+        ),
+        Extraction::Regex { pattern } => extract_regex(&body, pattern)?,
+        Extraction::Json {
+            generate_pointer,
+            consumption_pointer,
+        } => (
+            extract_json_pointer(&body, generate_pointer)?,
+            extract_json_pointer(&body, consumption_pointer)?,
+        ),
+    };
+
+    let generate = parse_scaled(&generate, config.scale)?;
+    let consumption = parse_scaled(&consumption, config.scale)?;
+
+    Ok((generate, consumption))
+}
+
+fn parse_scaled(raw: &str, scale: f64) -> Result<i64, Error> {
+    raw.parse::<f64>()
+        .map(|v| (v * scale).round() as i64)
+        .map_err(|e| Error::ParseFloat(raw.to_string(), e))
+}
+
+fn extract_marked(body: &str, start_marker: &str, end_marker: &str) -> Result<String, Error> {
+    body.lines()
+        .find_map(|line| {
+            let start = line.find(start_marker)? + start_marker.len();
+            let end = line[start..].find(end_marker)?;
+
+            Some(line[start..start + end].to_string())
+        })
+        .ok_or_else(|| Error::MarkerMissing(start_marker.to_string()))
+}
+
+fn extract_regex(body: &str, pattern: &str) -> Result<(String, String), Error> {
+    let regex = Regex::new(pattern).map_err(|e| Error::MarkerMissing(e.to_string()))?;
+
+    let captures = regex
+        .captures(body)
+        .ok_or_else(|| Error::MarkerMissing(pattern.to_string()))?;
+
+    let generate = captures
+        .name("generate")
+        .ok_or_else(|| Error::MarkerMissing("generate".to_string()))?
+        .as_str()
+        .to_string();
+    let consumption = captures
+        .name("consumption")
+        .ok_or_else(|| Error::MarkerMissing("consumption".to_string()))?
+        .as_str()
+        .to_string();
+
+    Ok((generate, consumption))
+}
+
+fn extract_json_pointer(body: &str, pointer: &str) -> Result<String, Error> {
+    let value: Value = serde_json::from_str(body)?;
+
+    let found = value
+        .pointer(pointer)
+        .ok_or_else(|| Error::MarkerMissing(pointer.to_string()))?;
+
+    match found {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        _ => Err(Error::MarkerMissing(pointer.to_string())),
+    }
+}